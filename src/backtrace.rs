@@ -0,0 +1,123 @@
+//! Symbolicated backtraces, printed when the target core halts (on a `bkpt`,
+//! panic, or HardFault). Unwinds the stack using the ELF's DWARF CFI
+//! (`.debug_frame`) and resolves each return address via `addr2line`.
+
+use std::path::Path;
+
+use addr2line::gimli;
+use anyhow::{Context, Result};
+use object::{Object, ObjectSection};
+use probe_rs::{Core, MemoryInterface, RegisterId};
+
+/// Frames beyond this depth mean the unwinder lost the stack, not that the target
+/// genuinely recursed this deep.
+const MAX_FRAMES: usize = 50;
+
+/// Reads PC/LR/SP, unwinds the call stack via CFI, and prints each frame's function
+/// name and `file:line`, top (the halt site) to bottom.
+pub fn print(core: &mut Core, elf_path: &Path) -> Result<()> {
+    let elf_data = std::fs::read(elf_path)
+        .with_context(|| format!("failed to read ELF file `{}`", elf_path.display()))?;
+    let object_file = object::File::parse(&*elf_data).context("failed to parse ELF file")?;
+    let debug_frame_data = object_file
+        .section_by_name(".debug_frame")
+        .and_then(|section| section.uncompressed_data().ok())
+        .unwrap_or_default();
+    let debug_frame = gimli::DebugFrame::new(&debug_frame_data, gimli::NativeEndian);
+    let bases = gimli::BaseAddresses::default();
+
+    let loader = addr2line::Loader::new(elf_path)
+        .map_err(|e| anyhow::anyhow!("failed to load debug info from `{}`: {e}", elf_path.display()))?;
+
+    let mut pc: u64 = core.read_core_reg(core.program_counter())?;
+    let mut lr: u64 = core.read_core_reg(core.return_address())?;
+    let mut sp: u64 = core.read_core_reg(core.stack_pointer())?;
+
+    println!("stack backtrace:");
+    let mut unwind_ctx = gimli::UnwindContext::new();
+    for frame in 0..MAX_FRAMES {
+        print_frame(&loader, frame, pc);
+
+        if is_reset_or_main(&loader, pc) {
+            break;
+        }
+
+        let row = match debug_frame.unwind_info_for_address(
+            &bases,
+            &mut unwind_ctx,
+            pc,
+            gimli::DebugFrame::cie_from_offset,
+        ) {
+            Ok(row) => row,
+            // No CFI for this address (e.g. inside an exception handler); fall back to
+            // the link register since the stack layout can no longer be trusted.
+            Err(_) => {
+                if lr == 0 || lr == pc {
+                    break;
+                }
+                pc = lr & !1;
+                continue;
+            }
+        };
+
+        let cfa = match *row.cfa() {
+            gimli::CfaRule::RegisterAndOffset { register, offset } => {
+                let reg_value = if register == gimli::Arm::SP {
+                    sp
+                } else {
+                    core.read_core_reg(RegisterId(register.0))?
+                };
+                (reg_value as i64 + offset) as u64
+            }
+            gimli::CfaRule::Expression(_) => break,
+        };
+
+        let next_lr = read_saved_register(core, &row, gimli::Arm::LR, cfa)?.unwrap_or(lr);
+        if next_lr == 0 || cfa <= sp {
+            break;
+        }
+
+        pc = next_lr & !1; // clear the Thumb bit
+        lr = next_lr;
+        sp = cfa;
+    }
+
+    Ok(())
+}
+
+fn read_saved_register<R: gimli::ReaderOffset>(
+    core: &mut Core,
+    row: &gimli::UnwindTableRow<R>,
+    register: gimli::Register,
+    cfa: u64,
+) -> Result<Option<u64>> {
+    match row.register(register) {
+        gimli::RegisterRule::Offset(offset) => {
+            let address = (cfa as i64 + offset) as u64;
+            let mut buf = [0u8; 4];
+            core.read_8(address, &mut buf)?;
+            Ok(Some(u32::from_le_bytes(buf) as u64))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn print_frame(loader: &addr2line::Loader, index: usize, pc: u64) {
+    let name = loader
+        .find_symbol(pc)
+        .map(|name| addr2line::demangle_auto(name.into(), None).to_string())
+        .unwrap_or_else(|| "<unknown>".to_string());
+
+    println!("{index:>4}: {name}");
+    if let Ok(Some(location)) = loader.find_location(pc) {
+        let file = location.file.unwrap_or("<unknown>");
+        println!("      at {file}:{}", location.line.unwrap_or(0));
+    }
+}
+
+fn is_reset_or_main(loader: &addr2line::Loader, pc: u64) -> bool {
+    matches!(
+        loader.find_symbol(pc),
+        Some("main") | Some("Reset") | Some("_start")
+    )
+}
@@ -0,0 +1,93 @@
+//! Stack-overflow detection via a stack canary.
+//!
+//! Adapted from `probe-run`'s canary: while the core is halted, the unused part of
+//! RAM below the current stack pointer is painted with a known byte pattern. Once the
+//! core halts again, the painted region is read back; scanning up from its bottom, the
+//! first byte that no longer holds the pattern marks the stack's high-water mark.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use object::{Object, ObjectSection, SectionKind};
+use probe_rs::config::MemoryRegion;
+use probe_rs::{Core, MemoryInterface};
+
+const CANARY_BYTE: u8 = 0xAA;
+
+/// Windows smaller than this aren't worth painting; the measurement would be noise.
+const MIN_STACK_WINDOW: u64 = 16;
+
+pub struct Canary {
+    low_address: u64,
+    size: u64,
+}
+
+/// Paints the unused RAM between the end of the ELF's static data (`.data`/`.bss`)
+/// and the current stack pointer, returning `None` if that window is too small to
+/// produce a meaningful measurement. Bounding the paint at the end of static data
+/// keeps this from overwriting `.data`/`.bss`/heap of the already-running target.
+pub fn paint(core: &mut Core, memory_map: &[MemoryRegion], elf_path: &Path) -> Result<Option<Canary>> {
+    let sp: u64 = core.read_core_reg(core.stack_pointer())?;
+
+    let ram = memory_map
+        .iter()
+        .filter_map(MemoryRegion::as_ram_region)
+        .find(|ram| ram.range.contains(&sp))
+        .context("stack pointer is not within a known RAM region")?;
+
+    let low_address = static_data_end(elf_path)?.max(ram.range.start);
+    if low_address >= sp {
+        return Ok(None);
+    }
+    let size = sp - low_address;
+
+    if size < MIN_STACK_WINDOW {
+        return Ok(None);
+    }
+
+    core.write_8(low_address, &vec![CANARY_BYTE; size as usize])?;
+
+    Ok(Some(Canary { low_address, size }))
+}
+
+/// Returns the end address (one past the last byte) of the ELF's `.data`/`.bss`
+/// sections, i.e. the lowest address that's safe to paint without clobbering live
+/// static memory.
+fn static_data_end(elf_path: &Path) -> Result<u64> {
+    let elf_data = fs::read(elf_path)
+        .with_context(|| format!("failed to read ELF file `{}`", elf_path.display()))?;
+    let object_file = object::File::parse(&*elf_data).context("failed to parse ELF file")?;
+
+    Ok(object_file
+        .sections()
+        .filter(|section| {
+            matches!(
+                section.kind(),
+                SectionKind::Data | SectionKind::UninitializedData
+            )
+        })
+        .map(|section| section.address() + section.size())
+        .max()
+        .unwrap_or(0))
+}
+
+impl Canary {
+    /// Reads the painted region back and returns the measured maximum stack usage,
+    /// in bytes. Prints a warning if the painted window was fully consumed.
+    pub fn measure(&self, core: &mut Core) -> Result<u64> {
+        let mut buf = vec![0u8; self.size as usize];
+        core.read_8(self.low_address, &mut buf)?;
+
+        let untouched = buf.iter().take_while(|&&b| b == CANARY_BYTE).count() as u64;
+        let used = self.size - untouched;
+
+        if untouched == 0 {
+            eprintln!(
+                "warning: possible stack overflow - the painted stack region was fully consumed"
+            );
+        }
+
+        Ok(used)
+    }
+}
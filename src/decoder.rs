@@ -0,0 +1,48 @@
+//! Decoding of `defmt`-framed RTT output, driven by an ELF file's `.defmt` table.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use defmt_decoder::{Frame, Locations, Table};
+
+/// Parses the interned-string table and call-site locations out of an ELF file.
+pub fn load_table(elf_path: &Path) -> Result<(Table, Option<Locations>)> {
+    let elf = fs::read(elf_path)
+        .with_context(|| format!("failed to read ELF file `{}`", elf_path.display()))?;
+    let table = Table::parse(&elf)
+        .context("failed to parse the `.defmt` table from the ELF file")?
+        .context("ELF file does not contain a `.defmt` table; was it built with defmt?")?;
+    let locations = table
+        .get_locations(&elf)
+        .ok()
+        .filter(|locations| !locations.is_empty());
+    Ok((table, locations))
+}
+
+/// Prints a decoded `defmt` frame: its timestamp (if the firmware logs one), level,
+/// formatted message, and call-site location when one resolves.
+pub fn print_frame(frame: &Frame, locations: &Option<Locations>) {
+    let level = frame
+        .level()
+        .map(|level| level.as_str())
+        .unwrap_or("-");
+
+    let prefix = match frame.display_timestamp() {
+        Some(timestamp) => format!("{timestamp} {level}"),
+        None => level.to_string(),
+    };
+
+    match locations
+        .as_ref()
+        .and_then(|locations| locations.get(&frame.index()))
+    {
+        Some(location) => println!(
+            "{prefix} {} @ {}:{}",
+            frame.display_message(),
+            location.file.display(),
+            location.line
+        ),
+        None => println!("{prefix} {}", frame.display_message()),
+    }
+}
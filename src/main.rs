@@ -1,64 +1,177 @@
+mod backtrace;
+mod canary;
+mod decoder;
+mod rtt_attach;
 mod session;
 
-use std::io;
+use std::io::{self, Write};
 use std::time::Duration;
 
 use anyhow::{Context, Result};
-use ascii::ToAsciiChar;
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind};
-use crossterm::style::Print;
-use crossterm::ExecutableCommand;
-use probe_rs::rtt::Rtt;
+use defmt_decoder::DecodeError;
+use probe_rs::rtt::{ChannelMode, DownChannel, Rtt, RttChannel, UpChannel};
 
 fn main() -> Result<()> {
-    let mut session = session::get_session()?;
+    let (mut session, opts) = session::get_session()?;
 
     let memory_map = session.target().memory_map.clone();
     // Select a core.
-    let mut core = session.core(0)?;
+    let mut core = session.core(opts.core)?;
+
+    let canary = if opts.measure_stack {
+        let elf = opts.elf.as_deref().context(
+            "--measure-stack requires --elf, so the unused stack region can be bounded safely",
+        )?;
+        // Register reads and the paint itself require the core to be halted; resume
+        // it again once painting is done so the firmware actually runs afterwards.
+        core.halt(Duration::from_millis(500))
+            .context("failed to halt core to measure its stack")?;
+        let canary = canary::paint(&mut core, &memory_map, elf)?;
+        core.run()
+            .context("failed to resume core after painting stack canary")?;
+        canary
+    } else {
+        None
+    };
 
     // Attach to RTT
-    let mut rtt = Rtt::attach(&mut core, &memory_map)?;
-    let down_channel = rtt.down_channels().take(0).unwrap();
-    let up_channel = rtt.up_channels().take(0).unwrap();
+    let mut rtt = rtt_attach::attach(&mut core, &memory_map, opts.elf.as_deref())?;
+    let down_channel = take_down_channel(&mut rtt, &opts.down_channel)?;
+    let mut up_channel = take_up_channel(&mut rtt, &opts.up_channel)?;
+
+    let mode = if opts.blocking {
+        ChannelMode::BlockIfFull
+    } else {
+        ChannelMode::NoBlockSkip
+    };
+    up_channel.set_mode(&mut core, mode)?;
 
     let mut stdout = io::stdout();
 
+    // When an ELF is given, decode the up-channel as `defmt` frames instead of raw text.
+    let defmt_table = opts.elf.as_deref().map(decoder::load_table).transpose()?;
+    let mut stream_decoder = defmt_table
+        .as_ref()
+        .map(|(table, _)| table.new_stream_decoder());
+    let mut up_buf = [0u8; 1024];
+    let mut input_buf = Vec::new();
+
     loop {
+        if core.core_halted()? {
+            if let Some(canary) = &canary {
+                let used = canary.measure(&mut core)?;
+                println!("max stack usage: {used} bytes");
+            }
+            if let Some(elf) = &opts.elf {
+                backtrace::print(&mut core, elf)?;
+            }
+
+            // Detach cleanly before leaving: drop the RTT handle and core borrow
+            // before the session, then exit non-zero like `probe-run` does on halt.
+            drop(rtt);
+            drop(core);
+            drop(session);
+            std::process::exit(1);
+        }
+
         // terminal -> RTT
-        if let Some(char) = read_char()? {
-            down_channel.write(&mut core, &[char.as_byte()])?;
+        input_buf.clear();
+        drain_input(&mut input_buf)?;
+        if !input_buf.is_empty() {
+            down_channel.write(&mut core, &input_buf)?;
         }
+
         // RTT -> terminal
-        let mut buf = [0];
-        let count = up_channel.read(&mut core, &mut buf)?;
-        if count > 0 {
-            if let Some(char) = buf[0].to_ascii_char().ok() {
-                stdout
-                    .execute(Print(char))
-                    .context("ExecutableCommand::execute")?;
+        match (&defmt_table, &mut stream_decoder) {
+            (Some((table, locations)), Some(stream_decoder)) => {
+                let count = up_channel.read(&mut core, &mut up_buf)?;
+                if count > 0 {
+                    stream_decoder.received(&up_buf[..count]);
+                    loop {
+                        match stream_decoder.decode() {
+                            Ok(frame) => decoder::print_frame(&frame, locations),
+                            Err(DecodeError::UnexpectedEof) => break,
+                            Err(DecodeError::Malformed) => {
+                                eprintln!("defmt: malformed frame, resetting decoder");
+                                *stream_decoder = table.new_stream_decoder();
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {
+                let count = up_channel.read(&mut core, &mut up_buf)?;
+                if count > 0 {
+                    // Forward raw bytes rather than only printable ASCII, so accented
+                    // characters and box-drawing output survive.
+                    stdout
+                        .write_all(&up_buf[..count])
+                        .context("io::Write::write_all")?;
+                    stdout.flush().context("io::Write::flush")?;
+                }
             }
         }
     }
 }
 
-fn read_char() -> Result<Option<ascii::AsciiChar>> {
-    if !crossterm::event::poll(Duration::from_millis(1)).context("crossterm::event::poll")? {
-        return Ok(None);
+fn take_up_channel(rtt: &mut Rtt, selector: &Option<String>) -> Result<UpChannel> {
+    let index = resolve_channel_index(rtt.up_channels().iter(), selector)
+        .context("up channel not found")?;
+    rtt.up_channels()
+        .take(index)
+        .context("up channel not found")
+}
+
+fn take_down_channel(rtt: &mut Rtt, selector: &Option<String>) -> Result<DownChannel> {
+    let index = resolve_channel_index(rtt.down_channels().iter(), selector)
+        .context("down channel not found")?;
+    rtt.down_channels()
+        .take(index)
+        .context("down channel not found")
+}
+
+/// Resolves a channel selector to a channel index, matching by name first and
+/// falling back to treating the selector as a numeric index.
+fn resolve_channel_index<'a, C: RttChannel + 'a>(
+    channels: impl Iterator<Item = &'a C>,
+    selector: &Option<String>,
+) -> Option<usize> {
+    let channels: Vec<&C> = channels.collect();
+    match selector {
+        None => channels.first().map(|channel| channel.number()),
+        Some(selector) => channels
+            .iter()
+            .find(|channel| channel.name() == Some(selector.as_str()))
+            .map(|channel| channel.number())
+            .or_else(|| selector.parse().ok()),
+    }
+}
+
+/// Drains all terminal input currently available into `buf` as raw bytes, blocking
+/// briefly (to avoid busy-looping) only while no input has arrived yet.
+fn drain_input(buf: &mut Vec<u8>) -> Result<()> {
+    let mut timeout = Duration::from_millis(1);
+    while crossterm::event::poll(timeout).context("crossterm::event::poll")? {
+        if let Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            ..
+        }) = crossterm::event::read().context("crossterm::event::read()")?
+        {
+            match code {
+                KeyCode::Char(c) => {
+                    let mut char_buf = [0u8; 4];
+                    buf.extend_from_slice(c.encode_utf8(&mut char_buf).as_bytes());
+                }
+                KeyCode::Enter => buf.push(b'\n'),
+                KeyCode::Tab => buf.push(b'\t'),
+                _ => {}
+            }
+        }
+        // Subsequent events are already queued; don't wait for more.
+        timeout = Duration::ZERO;
     }
-    Ok(
-        match crossterm::event::read().context("crossterm::event::read()")? {
-            Event::Key(KeyEvent {
-                code,
-                kind: KeyEventKind::Press,
-                ..
-            }) => match code {
-                KeyCode::Char(c) => c.to_ascii_char().ok(),
-                KeyCode::Enter => Some(ascii::AsciiChar::LineFeed),
-                KeyCode::Tab => Some(ascii::AsciiChar::Tab),
-                _ => None,
-            },
-            _ => None,
-        },
-    )
+    Ok(())
 }
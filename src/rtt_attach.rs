@@ -0,0 +1,46 @@
+//! ELF-aware RTT attach: locates the `_SEGGER_RTT` control block via the ELF symbol
+//! table instead of scanning the whole memory map, and retries briefly so attach
+//! succeeds even if firmware hasn't initialized RTT yet right after a reset.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use object::{Object, ObjectSymbol};
+use probe_rs::config::MemoryRegion;
+use probe_rs::rtt::{Rtt, ScanRegion};
+use probe_rs::Core;
+
+const RETRY_TIMEOUT: Duration = Duration::from_millis(500);
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Attaches to RTT, scanning only the `_SEGGER_RTT` symbol's address when `elf` is
+/// given and contains that symbol, falling back to a full RAM scan otherwise.
+pub fn attach(core: &mut Core, memory_map: &[MemoryRegion], elf: Option<&Path>) -> Result<Rtt> {
+    let scan_region = elf
+        .and_then(|elf| control_block_address(elf).ok().flatten())
+        .map(ScanRegion::Exact)
+        .unwrap_or(ScanRegion::Ram);
+
+    let deadline = Instant::now() + RETRY_TIMEOUT;
+    loop {
+        match Rtt::attach_region(core, memory_map, &scan_region) {
+            Ok(rtt) => return Ok(rtt),
+            Err(err) if Instant::now() < deadline => {
+                std::thread::sleep(RETRY_INTERVAL);
+                let _ = err;
+            }
+            Err(err) => return Err(err).context("failed to attach to RTT"),
+        }
+    }
+}
+
+fn control_block_address(elf_path: &Path) -> Result<Option<u64>> {
+    let elf_data = std::fs::read(elf_path)
+        .with_context(|| format!("failed to read ELF file `{}`", elf_path.display()))?;
+    let object_file = object::File::parse(&*elf_data).context("failed to parse ELF file")?;
+    Ok(object_file
+        .symbols()
+        .find(|symbol| symbol.name() == Ok("_SEGGER_RTT"))
+        .map(|symbol| symbol.address()))
+}
@@ -7,7 +7,7 @@ use std::str::FromStr;
 
 use clap::Parser;
 use probe_rs::DebugProbeError::ProbeSpecific;
-use probe_rs::{DebugProbeInfo, Permissions, Probe, Session};
+use probe_rs::{DebugProbeInfo, Permissions, Probe, Session, WireProtocol};
 
 /// A RTT console
 #[derive(Parser)]
@@ -32,13 +32,43 @@ pub struct Opts {
     /// The probe clock frequency in kHz
     #[arg(long)]
     speed: Option<u32>,
+
+    /// The protocol to connect to the chip with.
+    #[arg(long)]
+    protocol: Option<WireProtocol>,
+
+    /// Path to an ELF file built with `defmt`. When given, RTT output is decoded as
+    /// `defmt` frames instead of being forwarded as raw bytes.
+    #[arg(long)]
+    pub elf: Option<PathBuf>,
+
+    /// Measure the target's stack usage with a stack canary and warn on overflow.
+    #[arg(long)]
+    pub measure_stack: bool,
+
+    /// The core to attach RTT to.
+    #[arg(long, default_value_t = 0)]
+    pub core: usize,
+
+    /// The up (target to host) RTT channel to use, by name or index.
+    #[arg(long)]
+    pub up_channel: Option<String>,
+
+    /// The down (host to target) RTT channel to use, by name or index.
+    #[arg(long)]
+    pub down_channel: Option<String>,
+
+    /// Block the target when the up channel's buffer is full, instead of dropping
+    /// data. Lossless, but can stall the target if the host falls behind.
+    #[arg(long)]
+    pub blocking: bool,
 }
 
-pub fn get_session() -> anyhow::Result<Session> {
+pub fn get_session() -> anyhow::Result<(Session, Opts)> {
     let opts = Opts::parse();
     let probe_target = lookup_probe_target(&opts.chip, &opts)?;
     let sess = attach_to_probe(probe_target.clone(), &opts)?;
-    Ok(sess)
+    Ok((sess, opts))
 }
 
 fn lookup_probe_target(chip_name: &str, opts: &Opts) -> anyhow::Result<probe_rs::Target> {
@@ -107,6 +137,10 @@ pub fn open(opts: &Opts) -> Result<Probe, anyhow::Error> {
         probe.set_speed(speed)?;
     }
 
+    if let Some(protocol) = opts.protocol {
+        probe.select_protocol(protocol)?;
+    }
+
     Ok(probe)
 }
 